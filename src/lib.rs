@@ -16,9 +16,9 @@
 //! [`TlsStream`] and the dump can be written to STDOUT.
 //!
 //! For reproducing test cases, a `TcpStream` could be wrapped and the log
-//! written to a file. Then [fixture-io] can load the file and replay the data
-//! exchange. These replayable scenarios can be used as part of unit tests to
-//! help prevent regressions.
+//! written to a file. Then [`Replay`] can load the file and replay the data
+//! exchange, acting as a scripted mock stream. These replayable scenarios can
+//! be used as part of unit tests to help prevent regressions.
 //!
 //! # Usage
 //!
@@ -53,7 +53,11 @@
 //! **Note** that writing the log output is done using blocking I/O. So, writing
 //! to a file could block the current thread if the disk is not ready. This
 //! could cause delays in non-blocking systems such as Tokio. As such, care
-//! should be taken when using `io-dump` in production systems.
+//! should be taken when using `io-dump` in production systems. For these cases
+//! [`Dump::buffered`] offloads the logging to a background writer thread so the
+//! hot path never blocks on the sink.
+//!
+//! [`Dump::buffered`]: struct.Dump.html#method.buffered
 //!
 //! # File format
 //!
@@ -98,6 +102,7 @@
 //! ```
 //!
 //! [`Dump`]: struct.Dump.html
+//! [`Replay`]: struct.Replay.html
 //! [`Read`]: https://doc.rust-lang.org/std/io/trait.Read.html
 //! [`Write`]: https://doc.rust-lang.org/std/io/trait.Write.html
 //! [`read`]: https://doc.rust-lang.org/std/io/trait.Read.html#tymethod.read
@@ -107,38 +112,189 @@
 
 #![deny(warnings, missing_docs, missing_debug_implementations)]
 
+#[cfg(feature = "tokio")]
+extern crate bytes;
+
 #[cfg(feature = "tokio")]
 extern crate futures;
 
 #[cfg(feature = "tokio")]
 extern crate tokio_io;
 
+#[cfg(feature = "tokio")]
+extern crate tokio_timer;
+
+#[cfg(feature = "tokio")]
+pub use tokio::DumpDecoder;
+
 use std::cmp;
+use std::collections::VecDeque;
 use std::fs::File;
-use std::io::{self, Read, Write, BufRead, BufReader, Lines};
+use std::io::{self, Read, Write, BufRead, BufReader, IoSlice, IoSliceMut};
 use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, SyncSender};
+use std::thread::{self, JoinHandle};
 use std::time::{Instant, Duration};
 
 /// Wraps an I/O handle, logging all activity in a readable format to a
 /// configurable destination.
 ///
+/// The on-disk layout is controlled by `F`, which defaults to
+/// [`HexFormatter`], the human readable hex/ASCII format. Alternate formatters
+/// such as [`JsonLinesFormatter`] and [`RawFormatter`] can be selected with
+/// [`with_formatter`](#method.with_formatter).
+///
 /// See [library level documentation](index.html) for more details.
+///
+/// [`HexFormatter`]: struct.HexFormatter.html
+/// [`JsonLinesFormatter`]: struct.JsonLinesFormatter.html
+/// [`RawFormatter`]: struct.RawFormatter.html
 #[derive(Debug)]
-pub struct Dump<T, U> {
+pub struct Dump<T, U, F = HexFormatter> {
     upstream: T,
-    inner: Option<Inner<U>>,
+    inner: Option<Inner<U, F>>,
 }
 
 #[derive(Debug)]
-struct Inner<U> {
-    dump: U,
+struct Inner<U, F> {
+    sink: Sink<U>,
+    formatter: F,
     now: Instant,
 }
 
+#[derive(Debug)]
+enum Sink<U> {
+    // Both directions are logged to a single destination.
+    Both(U),
+
+    // Each direction is logged to its own, optional, destination.
+    Split {
+        read: Option<U>,
+        write: Option<U>,
+    },
+
+    // Packets are handed to a background writer thread, which formats them,
+    // so the hot path never blocks on the underlying sink.
+    Buffered(BufferedSink),
+}
+
+// A packet handed to the background writer thread, formatted there rather than
+// on the caller's hot path.
+#[derive(Debug)]
+struct Queued {
+    dir: Direction,
+    elapsed: Duration,
+    data: Vec<u8>,
+}
+
+// Bounded queue feeding a dedicated writer thread that owns the sink.
+#[derive(Debug)]
+struct BufferedSink {
+    tx: Option<SyncSender<Queued>>,
+    dropped: Arc<AtomicUsize>,
+    handle: Option<JoinHandle<()>>,
+}
+
+// Number of packets that can be queued before the hot path starts
+// dropping (and counting) them.
+const BUFFER_CAPACITY: usize = 1024;
+
+// Flush the sink once this many packets have been written.
+const BUFFER_BATCH: usize = 64;
+
+impl BufferedSink {
+    fn spawn<U, F>(mut dump: U, mut formatter: F) -> BufferedSink
+        where U: Write + Send + 'static,
+              F: Formatter + Send + 'static,
+    {
+        let (tx, rx) = mpsc::sync_channel::<Queued>(BUFFER_CAPACITY);
+        let dropped = Arc::new(AtomicUsize::new(0));
+        let counter = dropped.clone();
+
+        let handle = thread::spawn(move || {
+            let mut batch = 0;
+
+            loop {
+                match rx.recv() {
+                    Ok(packet) => {
+                        note_dropped(&mut dump, &mut formatter, &counter);
+
+                        let wrote = formatter
+                            .write_packet(&mut dump, packet.dir, packet.elapsed, &packet.data);
+
+                        if wrote.is_err() {
+                            return;
+                        }
+
+                        batch += 1;
+
+                        if batch >= BUFFER_BATCH {
+                            let _ = dump.flush();
+                            batch = 0;
+                        }
+                    }
+                    Err(_) => {
+                        // The sender has gone away; flush the tail and exit.
+                        note_dropped(&mut dump, &mut formatter, &counter);
+                        let _ = dump.flush();
+                        return;
+                    }
+                }
+            }
+        });
+
+        BufferedSink {
+            tx: Some(tx),
+            dropped: dropped,
+            handle: Some(handle),
+        }
+    }
+
+    // Enqueue a packet for the worker to format. On a full queue the packet is
+    // dropped and counted rather than blocking the caller.
+    fn send(&self, packet: Queued) {
+        if let Some(tx) = self.tx.as_ref() {
+            if tx.try_send(packet).is_err() {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+impl Drop for BufferedSink {
+    fn drop(&mut self) {
+        // Closing the channel tells the worker to drain and flush the tail.
+        self.tx.take();
+
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+// If any packets were dropped since the last write, ask the formatter to note
+// the gap so the marker matches the capture's format.
+fn note_dropped<U: Write, F: Formatter>(dump: &mut U, formatter: &mut F, dropped: &AtomicUsize) {
+    let n = dropped.swap(0, Ordering::Relaxed);
+
+    if n > 0 {
+        let _ = formatter.write_dropped(dump, n);
+    }
+}
+
 /// Read the contents of a dump
+///
+/// The format is controlled by `F`, matching the [`Formatter`] used to write
+/// the dump. It defaults to [`HexFormatter`].
+///
+/// [`Formatter`]: trait.Formatter.html
+/// [`HexFormatter`]: struct.HexFormatter.html
 #[derive(Debug)]
-pub struct DumpRead<T> {
-    lines: Lines<BufReader<T>>,
+pub struct DumpRead<T, F = HexFormatter> {
+    src: BufReader<T>,
+    formatter: F,
 }
 
 /// Unit of data either read or written.
@@ -209,20 +365,81 @@ impl<T> Dump<T, io::Stdout> {
     }
 }
 
-impl<T, U: Write> Dump<T, U> {
+impl<T, U: Write> Dump<T, U, HexFormatter> {
     /// Create a new `Dump` wrapping `upstream` logging activity to `dump`.
-    pub fn new(upstream: T, dump: U) -> Dump<T, U> {
+    pub fn new(upstream: T, dump: U) -> Dump<T, U, HexFormatter> {
+        Dump::with_formatter(upstream, dump, HexFormatter::new())
+    }
+
+    /// Create a new `Dump` logging each direction to an independent
+    /// destination.
+    ///
+    /// Reads are logged to `read_dump` and writes to `write_dump`. This makes
+    /// it trivial to capture just one direction of a protocol exchange, for
+    /// example writing reads to a file and writes to STDERR.
+    pub fn split(upstream: T, read_dump: U, write_dump: U) -> Dump<T, U, HexFormatter> {
+        Dump {
+            upstream: upstream,
+            inner: Some(Inner {
+                sink: Sink::Split {
+                    read: Some(read_dump),
+                    write: Some(write_dump),
+                },
+                formatter: HexFormatter::new(),
+                now: Instant::now(),
+            }),
+        }
+    }
+}
+
+impl<T, U: Write + Send + 'static> Dump<T, U, HexFormatter> {
+    /// Create a new `Dump` that logs to `dump` from a background writer thread.
+    ///
+    /// Each packet is handed to a bounded queue drained by a dedicated thread
+    /// that owns `dump` and formats the packets, so the `read`/`write` hot path only
+    /// does an in-memory enqueue and never blocks on the underlying sink. When
+    /// the queue is full packets are dropped and counted, and a
+    /// `// N packets dropped` marker is emitted into the capture. The queue is
+    /// drained and flushed when the `Dump` is dropped.
+    pub fn buffered(upstream: T, dump: U) -> Dump<T, U, HexFormatter> {
+        Dump::buffered_with_formatter(upstream, dump, HexFormatter::new())
+    }
+}
+
+impl<T, U: Write, F: Formatter> Dump<T, U, F> {
+    /// Create a new `Dump` that logs activity to `dump` using `formatter`.
+    pub fn with_formatter(upstream: T, dump: U, formatter: F) -> Dump<T, U, F> {
+        Dump {
+            upstream: upstream,
+            inner: Some(Inner {
+                sink: Sink::Both(dump),
+                formatter: formatter,
+                now: Instant::now(),
+            }),
+        }
+    }
+
+    /// Create a new `Dump` that logs to `dump` from a background writer thread
+    /// using `formatter`. See [`buffered`](#method.buffered) for details.
+    pub fn buffered_with_formatter(upstream: T, dump: U, formatter: F) -> Dump<T, U, F>
+        where U: Send + 'static,
+              F: Clone + Send + 'static,
+    {
         Dump {
             upstream: upstream,
             inner: Some(Inner {
-                dump: dump,
+                // The worker owns a clone of the formatter and renders packets
+                // off the hot path; the copy held by `Inner` is unused for a
+                // buffered sink but keeps the type uniform.
+                sink: Sink::Buffered(BufferedSink::spawn(dump, formatter.clone())),
+                formatter: formatter,
                 now: Instant::now(),
             }),
         }
     }
 
     /// Create a new `Dump` that passes packets through without logging.
-    pub fn noop(upstream: T) -> Dump<T, U> {
+    pub fn noop(upstream: T) -> Dump<T, U, F> {
         Dump {
             upstream: upstream,
             inner: None,
@@ -230,7 +447,7 @@ impl<T, U: Write> Dump<T, U> {
     }
 }
 
-impl<T: Read, U: Write> Read for Dump<T, U> {
+impl<T: Read, U: Write, F: Formatter> Read for Dump<T, U, F> {
     fn read(&mut self, dst: &mut [u8]) -> io::Result<usize> {
         let n = try!(self.upstream.read(dst));
 
@@ -240,9 +457,27 @@ impl<T: Read, U: Write> Read for Dump<T, U> {
 
         Ok(n)
     }
+
+    // Note: the matching `is_read_vectored` capability query is deliberately
+    // not forwarded. It is unstable (`can_vector`, rust-lang/rust#69941) and
+    // overriding it would fail to compile on stable, so callers see the default
+    // `false`. The scatter/gather fast path below still works; only the
+    // capability hint is lost until the std API stabilizes.
+    fn read_vectored(&mut self, dst: &mut [IoSliceMut]) -> io::Result<usize> {
+        let n = try!(self.upstream.read_vectored(dst));
+
+        if let Some(inner) = self.inner.as_mut() {
+            // Log exactly the bytes that were filled, regardless of how they
+            // were scattered across `dst`.
+            let data = gather(dst.iter().map(|b| &**b), n);
+            try!(inner.write_packet(Direction::Read, &data));
+        }
+
+        Ok(n)
+    }
 }
 
-impl<T: Write, U: Write> Write for Dump<T, U> {
+impl<T: Write, U: Write, F: Formatter> Write for Dump<T, U, F> {
     fn write(&mut self, src: &[u8]) -> io::Result<usize> {
         let n = try!(self.upstream.write(src));
 
@@ -253,97 +488,175 @@ impl<T: Write, U: Write> Write for Dump<T, U> {
         Ok(n)
     }
 
+    // Note: as with `is_read_vectored`, the `is_write_vectored` capability
+    // query is not forwarded because it is unstable (`can_vector`); the
+    // vectored write itself is still forwarded below.
+    fn write_vectored(&mut self, src: &[IoSlice]) -> io::Result<usize> {
+        let n = try!(self.upstream.write_vectored(src));
+
+        if let Some(inner) = self.inner.as_mut() {
+            // Log exactly the bytes the upstream accepted as a single packet.
+            let data = gather(src.iter().map(|b| &**b), n);
+            try!(inner.write_packet(Direction::Write, &data));
+        }
+
+        Ok(n)
+    }
+
     fn flush(&mut self) -> io::Result<()> {
         try!(self.upstream.flush());
         Ok(())
     }
 }
 
-// ===== impl Inner =====
-
-impl<U: Write> Inner<U> {
-    fn write_packet(&mut self, dir: Direction, data: &[u8]) -> io::Result<()> {
-        if dir == Direction::Write {
-            try!(write!(self.dump, "<-  "));
-        } else {
-            try!(write!(self.dump, "->  "));
-        }
-
-        // Write elapsed time
-        let elapsed = millis((Instant::now() - self.now)) as f64 / 1000.0;
-        try!(write!(self.dump, "{:.*}s  {} bytes", 3, elapsed, data.len()));
-
-        // Write newline
-        try!(write!(self.dump, "\n"));
-
-        let mut pos = 0;
-
-        while pos < data.len() {
-            let end = cmp::min(pos + LINE, data.len());
-            try!(self.write_data_line(&data[pos..end]));
-            pos = end;
+// Collect the first `n` bytes spanning `bufs` into a single contiguous buffer,
+// so a scatter/gather transfer is logged as one packet.
+fn gather<'a, I>(bufs: I, n: usize) -> Vec<u8>
+    where I: Iterator<Item = &'a [u8]>,
+{
+    let mut data = Vec::with_capacity(n);
+    let mut remaining = n;
+
+    for buf in bufs {
+        if remaining == 0 {
+            break;
         }
 
-        try!(write!(self.dump, "\n"));
-
-        Ok(())
+        let take = cmp::min(remaining, buf.len());
+        data.extend_from_slice(&buf[..take]);
+        remaining -= take;
     }
 
-    fn write_data_line(&mut self, line: &[u8]) -> io::Result<()> {
-        // First write binary
-        for i in 0..LINE {
-            if i >= line.len() {
-                try!(write!(self.dump, "   "));
-            } else {
-                try!(write!(self.dump, "{:02X} ", line[i]));
-            }
-        }
+    data
+}
 
-        // Write some spacing for the ascii
-        try!(write!(self.dump, "    "));
+// ===== impl Inner =====
 
-        for &byte in line.iter() {
-            match byte {
-                 0 => try!(write!(self.dump, "\\0")),
-                 9 => try!(write!(self.dump, "\\t")),
-                10 => try!(write!(self.dump, "\\n")),
-                13 => try!(write!(self.dump, "\\r")),
-                32...126 => {
-                    try!(self.dump.write(&[b' ', byte]));
+impl<U: Write, F: Formatter> Inner<U, F> {
+    fn write_packet(&mut self, dir: Direction, data: &[u8]) -> io::Result<()> {
+        // Elapsed time is measured the same regardless of which sink the
+        // packet is routed to.
+        let elapsed = Instant::now() - self.now;
+
+        let dump = match self.sink {
+            Sink::Both(ref mut dump) => Some(dump),
+            Sink::Split { ref mut read, ref mut write } => {
+                match dir {
+                    Direction::Read => read.as_mut(),
+                    Direction::Write => write.as_mut(),
                 }
-                _ => try!(write!(self.dump, "\\?")),
             }
+            Sink::Buffered(ref sink) => {
+                // Hand the raw packet to the worker thread, which owns the
+                // formatter and renders it off the hot path.
+                sink.send(Queued {
+                    dir: dir,
+                    elapsed: elapsed,
+                    data: data.to_vec(),
+                });
+                return Ok(());
+            }
+        };
+
+        if let Some(dump) = dump {
+            try!(self.formatter.write_packet(dump, dir, elapsed, data));
         }
 
-        write!(self.dump, "\n")
+        Ok(())
     }
 }
 
 /*
  *
- * ===== impl DumpRead =====
+ * ===== impl Formatter =====
  *
  */
 
-impl DumpRead<File> {
-    /// Open a dump file at the specified location.
-    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
-        let dump = try!(File::open(path));
-        Ok(DumpRead::new(dump))
+/// Controls how packets are rendered to, and parsed from, a dump.
+///
+/// A `Formatter` is a matched writer/reader pair: [`write_packet`] serializes a
+/// packet to a sink and [`read_packet`] parses the next packet back from a
+/// source. [`Dump`] writes with the formatter while [`DumpRead`] reads with it,
+/// so any format round-trips.
+///
+/// [`write_packet`]: #tymethod.write_packet
+/// [`read_packet`]: #tymethod.read_packet
+/// [`Dump`]: struct.Dump.html
+/// [`DumpRead`]: struct.DumpRead.html
+pub trait Formatter {
+    /// Write a single packet to `out`.
+    fn write_packet(&mut self, out: &mut Write, dir: Direction, elapsed: Duration, data: &[u8])
+        -> io::Result<()>;
+
+    /// Read the next packet from `src`, returning `None` at the end of input.
+    fn read_packet(&mut self, src: &mut BufRead) -> io::Result<Option<Packet>>;
+
+    /// Note that `n` packets were dropped by a buffered sink's full queue.
+    ///
+    /// The default is a no-op, which keeps formats that cannot carry an inline
+    /// comment (such as the length-prefixed [`RawFormatter`] or
+    /// [`JsonLinesFormatter`]) uncorrupted. [`HexFormatter`] overrides it to
+    /// write a `// N packets dropped` marker.
+    ///
+    /// [`RawFormatter`]: struct.RawFormatter.html
+    /// [`JsonLinesFormatter`]: struct.JsonLinesFormatter.html
+    /// [`HexFormatter`]: struct.HexFormatter.html
+    fn write_dropped(&mut self, _out: &mut Write, _n: usize) -> io::Result<()> {
+        Ok(())
     }
 }
 
-impl<T: Read> DumpRead<T> {
-    /// Reads dump packets from the specified source.
-    pub fn new(io: T) -> DumpRead<T> {
-        DumpRead { lines: BufReader::new(io).lines() }
+/// The default human readable hex/ASCII dump format.
+///
+/// See the [file format](index.html#file-format) section for a description of
+/// the layout.
+#[derive(Debug, Default, Clone)]
+pub struct HexFormatter {
+    _priv: (),
+}
+
+impl HexFormatter {
+    /// Create a new `HexFormatter`.
+    pub fn new() -> HexFormatter {
+        HexFormatter { _priv: () }
     }
+}
 
-    fn read_packet(&mut self) -> io::Result<Option<Packet>> {
+impl Formatter for HexFormatter {
+    fn write_packet(&mut self, out: &mut Write, dir: Direction, elapsed: Duration, data: &[u8])
+        -> io::Result<()>
+    {
+        let elapsed = millis(elapsed) as f64 / 1000.0;
+
+        if dir == Direction::Write {
+            try!(write!(out, "<-  "));
+        } else {
+            try!(write!(out, "->  "));
+        }
+
+        // Write elapsed time
+        try!(write!(out, "{:.*}s  {} bytes", 3, elapsed, data.len()));
+
+        // Write newline
+        try!(write!(out, "\n"));
+
+        let mut pos = 0;
+
+        while pos < data.len() {
+            let end = cmp::min(pos + LINE, data.len());
+            try!(write_data_line(out, &data[pos..end]));
+            pos = end;
+        }
+
+        try!(write!(out, "\n"));
+
+        Ok(())
+    }
+
+    fn read_packet(&mut self, src: &mut BufRead) -> io::Result<Option<Packet>> {
         loop {
-            let head = match self.lines.next() {
-                Some(Ok(line)) => line,
-                Some(Err(e)) => return Err(e),
+            let head = match try!(read_line(src)) {
+                Some(line) => line,
                 None => return Ok(None),
             };
 
@@ -353,11 +666,13 @@ impl<T: Read> DumpRead<T> {
                 .map(|v| v.into())
                 .collect();
 
-            if head.len() == 0 || head[0] == "//" {
+            if head.is_empty() || head[0] == "//" {
                 continue;
             }
 
-            assert_eq!(4, head.len());
+            if head.len() != 4 {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "malformed dump header"));
+            }
 
             let dir = match &head[0][..] {
                 "<-" => Direction::Write,
@@ -367,18 +682,20 @@ impl<T: Read> DumpRead<T> {
 
             let elapsed: f64 = {
                 let s = &head[1];
-                s[..s.len()-1].parse().unwrap()
+                match s[..s.len() - 1].parse() {
+                    Ok(v) => v,
+                    Err(_) => return Err(io::Error::new(io::ErrorKind::InvalidData, "could not parse elapsed")),
+                }
             };
 
             // Do nothing w/ bytes for now
 
-            // ready body
+            // read body
             let mut data = vec![];
 
             loop {
-                let line = match self.lines.next() {
-                    Some(Ok(line)) => line,
-                    Some(Err(e)) => return Err(e),
+                let line = match try!(read_line(src)) {
+                    Some(line) => line,
                     None => "".into(),
                 };
 
@@ -394,8 +711,8 @@ impl<T: Read> DumpRead<T> {
 
                 let mut pos = 0;
 
-                loop {
-                    let c = &line[pos..pos+2];
+                while pos + 2 <= line.len() {
+                    let c = &line[pos..pos + 2];
 
                     if c == "  " {
                         break;
@@ -413,9 +730,315 @@ impl<T: Read> DumpRead<T> {
             }
         }
     }
+
+    fn write_dropped(&mut self, out: &mut Write, n: usize) -> io::Result<()> {
+        // The header parser skips any line whose first token is `//`, so this
+        // marker round-trips as a comment rather than a packet.
+        write!(out, "// {} packets dropped\n", n)
+    }
+}
+
+fn write_data_line(dump: &mut Write, line: &[u8]) -> io::Result<()> {
+    // First write binary
+    for i in 0..LINE {
+        if i >= line.len() {
+            try!(write!(dump, "   "));
+        } else {
+            try!(write!(dump, "{:02X} ", line[i]));
+        }
+    }
+
+    // Write some spacing for the ascii
+    try!(write!(dump, "    "));
+
+    for &byte in line.iter() {
+        match byte {
+             0 => try!(write!(dump, "\\0")),
+             9 => try!(write!(dump, "\\t")),
+            10 => try!(write!(dump, "\\n")),
+            13 => try!(write!(dump, "\\r")),
+            32...126 => {
+                try!(dump.write(&[b' ', byte]));
+            }
+            _ => try!(write!(dump, "\\?")),
+        }
+    }
+
+    write!(dump, "\n")
+}
+
+/// One JSON object per packet, newline delimited.
+///
+/// Each record is self-describing, e.g.
+/// `{"dir":"read","elapsed_ms":13,"len":9,"data":"000102..."}`, so dumps can be
+/// consumed by external tooling and `jq` pipelines. The payload is hex encoded.
+#[derive(Debug, Default, Clone)]
+pub struct JsonLinesFormatter {
+    _priv: (),
+}
+
+impl JsonLinesFormatter {
+    /// Create a new `JsonLinesFormatter`.
+    pub fn new() -> JsonLinesFormatter {
+        JsonLinesFormatter { _priv: () }
+    }
+}
+
+impl Formatter for JsonLinesFormatter {
+    fn write_packet(&mut self, out: &mut Write, dir: Direction, elapsed: Duration, data: &[u8])
+        -> io::Result<()>
+    {
+        let dir = match dir {
+            Direction::Read => "read",
+            Direction::Write => "write",
+        };
+
+        try!(write!(out, "{{\"dir\":\"{}\",\"elapsed_ms\":{},\"len\":{},\"data\":\"",
+                    dir, millis(elapsed), data.len()));
+
+        for &byte in data {
+            try!(write!(out, "{:02x}", byte));
+        }
+
+        write!(out, "\"}}\n")
+    }
+
+    fn read_packet(&mut self, src: &mut BufRead) -> io::Result<Option<Packet>> {
+        loop {
+            let line = match try!(read_line(src)) {
+                Some(line) => line,
+                None => return Ok(None),
+            };
+
+            if line.is_empty() {
+                continue;
+            }
+
+            let dir = match try!(json_str(&line, "dir")) {
+                "read" => Direction::Read,
+                "write" => Direction::Write,
+                _ => return Err(io::Error::new(io::ErrorKind::InvalidData, "invalid direction")),
+            };
+
+            let elapsed = try!(json_num(&line, "elapsed_ms"));
+            let data = try!(parse_hex(try!(json_str(&line, "data"))));
+
+            return Ok(Some(Packet {
+                head: Head {
+                    direction: dir,
+                    elapsed: Duration::from_millis(elapsed),
+                },
+                data: data,
+            }));
+        }
+    }
+}
+
+/// Length-prefixed binary frames, for compact high-volume captures.
+///
+/// Each frame is a one byte direction (`0` read, `1` write), a little-endian
+/// `u32` elapsed milliseconds, a little-endian `u32` payload length, then the
+/// raw payload bytes.
+#[derive(Debug, Default, Clone)]
+pub struct RawFormatter {
+    _priv: (),
+}
+
+impl RawFormatter {
+    /// Create a new `RawFormatter`.
+    pub fn new() -> RawFormatter {
+        RawFormatter { _priv: () }
+    }
 }
 
-impl<T: Read> Iterator for DumpRead<T> {
+impl Formatter for RawFormatter {
+    fn write_packet(&mut self, out: &mut Write, dir: Direction, elapsed: Duration, data: &[u8])
+        -> io::Result<()>
+    {
+        let dir = match dir {
+            Direction::Read => 0u8,
+            Direction::Write => 1u8,
+        };
+
+        let elapsed = cmp::min(millis(elapsed), u32::max_value() as u64) as u32;
+        let len = data.len() as u32;
+
+        try!(out.write_all(&[dir]));
+        try!(out.write_all(&u32_le(elapsed)));
+        try!(out.write_all(&u32_le(len)));
+        out.write_all(data)
+    }
+
+    fn read_packet(&mut self, src: &mut BufRead) -> io::Result<Option<Packet>> {
+        let mut head = [0; 9];
+
+        match try!(read_full(src, &mut head)) {
+            0 => return Ok(None),
+            9 => {}
+            _ => return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "truncated frame header")),
+        }
+
+        let dir = match head[0] {
+            0 => Direction::Read,
+            1 => Direction::Write,
+            _ => return Err(io::Error::new(io::ErrorKind::InvalidData, "invalid direction")),
+        };
+
+        let elapsed = le_u32(&head[1..5]) as u64;
+        let len = le_u32(&head[5..9]) as usize;
+
+        let mut data = vec![0; len];
+
+        if try!(read_full(src, &mut data)) != len {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "truncated frame payload"));
+        }
+
+        Ok(Some(Packet {
+            head: Head {
+                direction: dir,
+                elapsed: Duration::from_millis(elapsed),
+            },
+            data: data,
+        }))
+    }
+}
+
+// ===== formatter helpers =====
+
+// Read a single line, stripped of its trailing newline. Returns `None` at the
+// end of input.
+fn read_line(src: &mut BufRead) -> io::Result<Option<String>> {
+    let mut line = String::new();
+
+    if try!(src.read_line(&mut line)) == 0 {
+        return Ok(None);
+    }
+
+    while line.ends_with('\n') || line.ends_with('\r') {
+        line.pop();
+    }
+
+    Ok(Some(line))
+}
+
+// Read exactly `buf.len()` bytes, returning the number actually read. A short
+// read only happens at end of input.
+fn read_full(src: &mut BufRead, buf: &mut [u8]) -> io::Result<usize> {
+    let mut pos = 0;
+
+    while pos < buf.len() {
+        let n = try!(src.read(&mut buf[pos..]));
+
+        if n == 0 {
+            break;
+        }
+
+        pos += n;
+    }
+
+    Ok(pos)
+}
+
+fn u32_le(v: u32) -> [u8; 4] {
+    [v as u8, (v >> 8) as u8, (v >> 16) as u8, (v >> 24) as u8]
+}
+
+fn le_u32(buf: &[u8]) -> u32 {
+    (buf[0] as u32)
+        | ((buf[1] as u32) << 8)
+        | ((buf[2] as u32) << 16)
+        | ((buf[3] as u32) << 24)
+}
+
+fn parse_hex(s: &str) -> io::Result<Vec<u8>> {
+    if s.len() % 2 != 0 {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "odd length hex payload"));
+    }
+
+    let mut data = Vec::with_capacity(s.len() / 2);
+    let mut pos = 0;
+
+    while pos < s.len() {
+        match u8::from_str_radix(&s[pos..pos + 2], 16) {
+            Ok(byte) => data.push(byte),
+            Err(_) => return Err(io::Error::new(io::ErrorKind::InvalidData, "could not parse byte")),
+        }
+
+        pos += 2;
+    }
+
+    Ok(data)
+}
+
+// Extract the string value of `key` from a flat JSON object line.
+fn json_str<'a>(line: &'a str, key: &str) -> io::Result<&'a str> {
+    let pat = format!("\"{}\":\"", key);
+    let start = match line.find(&pat) {
+        Some(i) => i + pat.len(),
+        None => return Err(io::Error::new(io::ErrorKind::InvalidData, "missing json field")),
+    };
+
+    match line[start..].find('"') {
+        Some(end) => Ok(&line[start..start + end]),
+        None => Err(io::Error::new(io::ErrorKind::InvalidData, "unterminated json string")),
+    }
+}
+
+// Extract the numeric value of `key` from a flat JSON object line.
+fn json_num(line: &str, key: &str) -> io::Result<u64> {
+    let pat = format!("\"{}\":", key);
+    let start = match line.find(&pat) {
+        Some(i) => i + pat.len(),
+        None => return Err(io::Error::new(io::ErrorKind::InvalidData, "missing json field")),
+    };
+
+    let end = line[start..]
+        .find(|c| c == ',' || c == '}')
+        .map(|i| start + i)
+        .unwrap_or(line.len());
+
+    match line[start..end].trim().parse() {
+        Ok(v) => Ok(v),
+        Err(_) => Err(io::Error::new(io::ErrorKind::InvalidData, "could not parse number")),
+    }
+}
+
+/*
+ *
+ * ===== impl DumpRead =====
+ *
+ */
+
+impl DumpRead<File, HexFormatter> {
+    /// Open a dump file at the specified location.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let dump = try!(File::open(path));
+        Ok(DumpRead::new(dump))
+    }
+}
+
+impl<T: Read> DumpRead<T, HexFormatter> {
+    /// Reads dump packets from the specified source.
+    pub fn new(io: T) -> DumpRead<T, HexFormatter> {
+        DumpRead::with_formatter(io, HexFormatter::new())
+    }
+}
+
+impl<T: Read, F: Formatter> DumpRead<T, F> {
+    /// Reads dump packets from `io` using `formatter`.
+    pub fn with_formatter(io: T, formatter: F) -> DumpRead<T, F> {
+        DumpRead {
+            src: BufReader::new(io),
+            formatter: formatter,
+        }
+    }
+
+    fn read_packet(&mut self) -> io::Result<Option<Packet>> {
+        self.formatter.read_packet(&mut self.src)
+    }
+}
+
+impl<T: Read, F: Formatter> Iterator for DumpRead<T, F> {
     type Item = Packet;
 
     fn next(&mut self) -> Option<Packet> {
@@ -423,6 +1046,239 @@ impl<T: Read> Iterator for DumpRead<T> {
     }
 }
 
+/*
+ *
+ * ===== impl Replay =====
+ *
+ */
+
+/// Replays a recorded dump as a mock I/O handle.
+///
+/// `Replay` turns a captured dump into a deterministic, scripted stream. The
+/// recorded [`Direction::Read`] packets are handed back, in order, to [`read`]
+/// calls, while [`write`] calls are validated byte-for-byte against the
+/// recorded [`Direction::Write`] packets. A write that does not match the next
+/// expected bytes fails with [`ErrorKind::InvalidData`], turning any captured
+/// exchange into a regression test without a second crate.
+///
+/// When the `tokio` feature is enabled, `Replay` also implements
+/// [`AsyncRead`]/[`AsyncWrite`] and, unless constructed with
+/// [`no_timing`](#method.no_timing), honors the recorded [`elapsed`] offsets:
+/// a read packet does not become ready until its recorded offset from the
+/// replay start has elapsed.
+///
+/// [`Direction::Read`]: enum.Direction.html#variant.Read
+/// [`Direction::Write`]: enum.Direction.html#variant.Write
+/// [`read`]: https://doc.rust-lang.org/std/io/trait.Read.html#tymethod.read
+/// [`write`]: https://doc.rust-lang.org/std/io/trait.Write.html#tymethod.write
+/// [`ErrorKind::InvalidData`]: https://doc.rust-lang.org/std/io/enum.ErrorKind.html
+/// [`elapsed`]: struct.Packet.html#method.elapsed
+/// [`AsyncRead`]: https://docs.rs/tokio-io/0.1/tokio_io/trait.AsyncRead.html
+/// [`AsyncWrite`]: https://docs.rs/tokio-io/0.1/tokio_io/trait.AsyncWrite.html
+#[derive(Debug)]
+pub struct Replay {
+    // Queued read packets and the offset into the front packet.
+    reads: VecDeque<Packet>,
+    read_pos: usize,
+
+    // Queued write expectations and the offset into the front packet.
+    writes: VecDeque<Packet>,
+    write_pos: usize,
+
+    // When `false`, recorded timings are ignored and data is delivered
+    // immediately.
+    timing: bool,
+
+    // Replay start, established lazily on the first timed poll.
+    start: Option<Instant>,
+
+    // Pending timer gating the next read packet.
+    #[cfg(feature = "tokio")]
+    delay: Option<::tokio_timer::Delay>,
+}
+
+impl Replay {
+    /// Build a `Replay` from a dump file located at `path`.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Replay> {
+        let dump = try!(DumpRead::open(path));
+        Replay::new(dump)
+    }
+
+    /// Build a `Replay` from a [`DumpRead`], honoring the recorded timings.
+    ///
+    /// A malformed dump surfaces as an error rather than a panic.
+    ///
+    /// [`DumpRead`]: struct.DumpRead.html
+    pub fn new<T: Read, F: Formatter>(dump: DumpRead<T, F>) -> io::Result<Replay> {
+        Replay::build(dump, true)
+    }
+
+    /// Build a `Replay` that ignores the recorded timings and delivers every
+    /// packet immediately. Useful for fast, deterministic unit tests.
+    pub fn no_timing<T: Read, F: Formatter>(dump: DumpRead<T, F>) -> io::Result<Replay> {
+        Replay::build(dump, false)
+    }
+
+    fn build<T: Read, F: Formatter>(mut dump: DumpRead<T, F>, timing: bool) -> io::Result<Replay> {
+        let mut packets = Vec::new();
+
+        // Consume packets through the fallible parse path so a malformed dump
+        // propagates instead of panicking.
+        while let Some(packet) = try!(dump.read_packet()) {
+            packets.push(packet);
+        }
+
+        // Deliver packets in the order they were recorded.
+        packets.sort_by_key(|p| p.elapsed());
+
+        let mut reads = VecDeque::new();
+        let mut writes = VecDeque::new();
+
+        for packet in packets {
+            match packet.direction() {
+                Direction::Read => reads.push_back(packet),
+                Direction::Write => writes.push_back(packet),
+            }
+        }
+
+        Ok(Replay {
+            reads: reads,
+            read_pos: 0,
+            writes: writes,
+            write_pos: 0,
+            timing: timing,
+            start: None,
+
+            #[cfg(feature = "tokio")]
+            delay: None,
+        })
+    }
+
+    // Copy as much of the front read packet into `dst` as fits, advancing the
+    // queue. Returns `0` at the end of the recorded reads.
+    fn read_ready(&mut self, dst: &mut [u8]) -> usize {
+        // Drop empty recorded reads; returning `Ok(0)` for one would look like
+        // EOF and abort the replay while later packets remain.
+        while self.reads.front().map(|p| p.data().is_empty()).unwrap_or(false) {
+            self.reads.pop_front();
+            self.read_pos = 0;
+        }
+
+        let n = match self.reads.front() {
+            Some(packet) => {
+                let remaining = &packet.data()[self.read_pos..];
+                let n = cmp::min(dst.len(), remaining.len());
+                dst[..n].copy_from_slice(&remaining[..n]);
+                n
+            }
+            None => return 0,
+        };
+
+        self.read_pos += n;
+
+        if self.read_pos == self.reads.front().unwrap().data().len() {
+            self.reads.pop_front();
+            self.read_pos = 0;
+        }
+
+        n
+    }
+
+    // Validate `src` against the queued write expectations, consuming matched
+    // packets. Returns the number of bytes accepted (always `src.len()` on
+    // success).
+    fn write_expect(&mut self, src: &[u8]) -> io::Result<usize> {
+        let mut consumed = 0;
+
+        while consumed < src.len() {
+            let matched = match self.writes.front() {
+                Some(packet) => {
+                    let expected = &packet.data()[self.write_pos..];
+                    let n = cmp::min(src.len() - consumed, expected.len());
+
+                    if src[consumed..consumed + n] != expected[..n] {
+                        return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                                  "write did not match recorded dump"));
+                    }
+
+                    n
+                }
+                None => {
+                    return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                              "unexpected write, no packets remaining"));
+                }
+            };
+
+            consumed += matched;
+            self.write_pos += matched;
+
+            if self.write_pos == self.writes.front().unwrap().data().len() {
+                self.writes.pop_front();
+                self.write_pos = 0;
+            }
+        }
+
+        Ok(consumed)
+    }
+
+    // Gate the next read on the recorded timing. With the `tokio` feature this
+    // arms a timer and returns `WouldBlock` until the front packet's recorded
+    // offset has elapsed; otherwise timing is ignored and data flows
+    // immediately.
+    #[cfg(feature = "tokio")]
+    fn poll_read_gate(&mut self) -> io::Result<()> {
+        use futures::Async;
+        use futures::Future;
+
+        if !self.timing || self.reads.is_empty() {
+            return Ok(());
+        }
+
+        let start = *self.start.get_or_insert_with(Instant::now);
+        let deadline = start + self.reads.front().unwrap().elapsed();
+
+        if Instant::now() >= deadline {
+            self.delay = None;
+            return Ok(());
+        }
+
+        if self.delay.is_none() {
+            self.delay = Some(tokio_timer::Delay::new(deadline));
+        }
+
+        match self.delay.as_mut().unwrap().poll() {
+            Ok(Async::Ready(())) => {
+                self.delay = None;
+                Ok(())
+            }
+            Ok(Async::NotReady) => Err(io::ErrorKind::WouldBlock.into()),
+            Err(e) => Err(io::Error::new(io::ErrorKind::Other, e)),
+        }
+    }
+
+    #[cfg(not(feature = "tokio"))]
+    fn poll_read_gate(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Read for Replay {
+    fn read(&mut self, dst: &mut [u8]) -> io::Result<usize> {
+        try!(self.poll_read_gate());
+        Ok(self.read_ready(dst))
+    }
+}
+
+impl Write for Replay {
+    fn write(&mut self, src: &[u8]) -> io::Result<usize> {
+        self.write_expect(src)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
 const NANOS_PER_MILLI: u32 = 1_000_000;
 const MILLIS_PER_SEC: u64 = 1_000;
 
@@ -439,18 +1295,213 @@ fn millis(duration: Duration) -> u64 {
 
 #[cfg(feature = "tokio")]
 mod tokio {
-    use super::Dump;
+    use super::{Dump, Direction, Formatter, Head, Packet, Replay};
 
-    use futures::Poll;
+    use bytes::BytesMut;
+    use futures::{Async, Poll};
     use tokio_io::{AsyncRead, AsyncWrite};
+    use tokio_io::codec::Decoder;
 
+    use std::str;
     use std::io::{self, Write};
+    use std::time::Duration;
 
-    impl<T: AsyncRead, U: Write> AsyncRead for Dump<T, U> {}
+    // The `AsyncRead`/`AsyncWrite` traits at this version do not expose
+    // vectored poll hooks; scatter/gather transfers are captured through the
+    // `read_vectored`/`write_vectored` implementations on the blocking traits,
+    // which async callers call directly. `poll_read`/`poll_write` go through
+    // the scalar `read`/`write`.
+    impl<T: AsyncRead, U: Write, F: Formatter> AsyncRead for Dump<T, U, F> {}
 
-    impl<T: AsyncWrite, U: Write> AsyncWrite for Dump<T, U> {
+    impl<T: AsyncWrite, U: Write, F: Formatter> AsyncWrite for Dump<T, U, F> {
         fn shutdown(&mut self) -> Poll<(), io::Error> {
             self.upstream.shutdown()
         }
     }
+
+    impl AsyncRead for Replay {}
+
+    impl AsyncWrite for Replay {
+        fn shutdown(&mut self) -> Poll<(), io::Error> {
+            Ok(Async::Ready(()))
+        }
+    }
+
+    /// Decodes a dump stream into [`Packet`] values as bytes become available.
+    ///
+    /// `DumpDecoder` is the non-blocking counterpart to [`DumpRead`]. It
+    /// implements [`tokio_io::codec::Decoder`], so a recorded dump can be parsed
+    /// directly off an asynchronous source:
+    ///
+    /// ```ignore
+    /// let packets = FramedRead::new(async_file, DumpDecoder::new());
+    /// ```
+    ///
+    /// [`Packet`]: ../struct.Packet.html
+    /// [`DumpRead`]: ../struct.DumpRead.html
+    /// [`tokio_io::codec::Decoder`]: https://docs.rs/tokio-io/0.1/tokio_io/codec/trait.Decoder.html
+    #[derive(Debug)]
+    pub struct DumpDecoder {
+        state: State,
+    }
+
+    #[derive(Debug)]
+    enum State {
+        // Waiting for the next packet header line.
+        Head,
+        // Accumulating the body of the packet described by the header.
+        Body {
+            head: Head,
+            len: usize,
+            data: Vec<u8>,
+        },
+    }
+
+    impl DumpDecoder {
+        /// Create a new `DumpDecoder` positioned at the start of a dump.
+        pub fn new() -> DumpDecoder {
+            DumpDecoder { state: State::Head }
+        }
+    }
+
+    impl Decoder for DumpDecoder {
+        type Item = Packet;
+        type Error = io::Error;
+
+        fn decode(&mut self, buf: &mut BytesMut) -> io::Result<Option<Packet>> {
+            loop {
+                match self.state {
+                    State::Head => {
+                        let line = match try!(take_line(buf)) {
+                            Some(line) => line,
+                            None => return Ok(None),
+                        };
+
+                        let head: Vec<&str> = line.split_whitespace().collect();
+
+                        if head.is_empty() || head[0] == "//" {
+                            // Blank line or comment, keep scanning.
+                            continue;
+                        }
+
+                        if head.len() != 4 {
+                            return Err(invalid_data("malformed dump header"));
+                        }
+
+                        let direction = match head[0] {
+                            "<-" => Direction::Write,
+                            "->" => Direction::Read,
+                            _ => return Err(invalid_data("invalid direction format")),
+                        };
+
+                        let elapsed: f64 = {
+                            let s = head[1];
+
+                            if !s.ends_with('s') {
+                                return Err(invalid_data("invalid elapsed format"));
+                            }
+
+                            match s[..s.len() - 1].parse() {
+                                Ok(v) => v,
+                                Err(_) => return Err(invalid_data("could not parse elapsed")),
+                            }
+                        };
+
+                        let len: usize = match head[2].parse() {
+                            Ok(v) => v,
+                            Err(_) => return Err(invalid_data("could not parse byte count")),
+                        };
+
+                        self.state = State::Body {
+                            head: Head {
+                                direction: direction,
+                                elapsed: Duration::from_millis((elapsed * 1000.0) as u64),
+                            },
+                            len: len,
+                            data: Vec::with_capacity(len),
+                        };
+
+                        // Read the body before attempting to emit a packet.
+                        continue;
+                    }
+                    State::Body { ref mut data, len, .. } => {
+                        let line = match try!(take_line(buf)) {
+                            Some(line) => line,
+                            None => return Ok(None),
+                        };
+
+                        if !line.is_empty() {
+                            try!(parse_data_line(&line, data));
+                        }
+
+                        if line.is_empty() || data.len() >= len {
+                            // Fall through to emit the packet below.
+                        } else {
+                            continue;
+                        }
+                    }
+                }
+
+                // The body is complete; emit the packet and reset.
+                if let State::Body { .. } = self.state {
+                    match ::std::mem::replace(&mut self.state, State::Head) {
+                        State::Body { head, data, .. } => {
+                            return Ok(Some(Packet {
+                                head: head,
+                                data: data,
+                            }));
+                        }
+                        _ => unreachable!(),
+                    }
+                }
+            }
+        }
+    }
+
+    fn invalid_data(msg: &'static str) -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidData, msg)
+    }
+
+    /// Split a single line (without the trailing newline) off the front of
+    /// `buf`. Returns `None` when `buf` does not yet hold a complete line,
+    /// requesting more data.
+    fn take_line(buf: &mut BytesMut) -> io::Result<Option<String>> {
+        match buf.iter().position(|&b| b == b'\n') {
+            Some(i) => {
+                let line = buf.split_to(i + 1);
+
+                let s = match str::from_utf8(&line[..i]) {
+                    Ok(s) => s,
+                    Err(_) => return Err(invalid_data("dump contained invalid utf-8")),
+                };
+
+                Ok(Some(s.trim_end().to_string()))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Parse the hex column of a single data line, appending the bytes to
+    /// `data`. Parsing stops at the two-space gap separating the hex column
+    /// from the ASCII representation.
+    fn parse_data_line(line: &str, data: &mut Vec<u8>) -> io::Result<()> {
+        let mut pos = 0;
+
+        while pos + 2 <= line.len() {
+            let c = &line[pos..pos + 2];
+
+            if c == "  " {
+                break;
+            }
+
+            match u8::from_str_radix(c, 16) {
+                Ok(byte) => data.push(byte),
+                Err(_) => return Err(invalid_data("could not parse byte")),
+            }
+
+            pos += 3;
+        }
+
+        Ok(())
+    }
 }
@@ -0,0 +1,45 @@
+#![cfg(feature = "tokio")]
+
+extern crate bytes;
+extern crate io_dump;
+extern crate tokio_io;
+
+use std::time::Duration;
+
+use bytes::BytesMut;
+use tokio_io::codec::Decoder;
+
+use io_dump::{Direction, DumpDecoder, Formatter, HexFormatter};
+
+// A `Decoder` yields a packet only once its bytes have fully arrived, and must
+// report `None` (asking for more data) while the buffer is incomplete.
+#[test]
+fn frames_packet_across_partial_buffers() {
+    // Re-use the writer so the bytes match exactly what the decoder parses.
+    let mut dump = Vec::new();
+    HexFormatter::new()
+        .write_packet(&mut dump, Direction::Read, Duration::from_millis(13), b"hi")
+        .unwrap();
+
+    // A prefix that does not yet contain a full line decodes to `None`.
+    let mut decoder = DumpDecoder::new();
+    let mut buf = BytesMut::new();
+    buf.extend_from_slice(&dump[..3]);
+    assert!(decoder.decode(&mut buf).unwrap().is_none());
+
+    // Feeding the rest one byte at a time eventually yields exactly one packet.
+    let mut packet = None;
+
+    for &byte in &dump[3..] {
+        buf.extend_from_slice(&[byte]);
+
+        if let Some(p) = decoder.decode(&mut buf).unwrap() {
+            assert!(packet.is_none(), "decoded more than one packet");
+            packet = Some(p);
+        }
+    }
+
+    let packet = packet.expect("decoder never produced a packet");
+    assert_eq!(packet.direction(), Direction::Read);
+    assert_eq!(packet.data(), b"hi");
+}
@@ -0,0 +1,47 @@
+extern crate io_dump;
+
+use std::io::Cursor;
+use std::time::Duration;
+
+use io_dump::{Direction, DumpRead, Formatter, HexFormatter, JsonLinesFormatter, RawFormatter};
+
+// Write a fixed set of packets with `writer`, then parse them back with
+// `reader`, asserting every field survives the round-trip.
+fn round_trip<F: Formatter>(mut writer: F, reader: F) {
+    let packets: Vec<(Direction, Duration, Vec<u8>)> = vec![
+        (Direction::Read, Duration::from_millis(13), b"hello".to_vec()),
+        (Direction::Write, Duration::from_millis(40), Vec::new()),
+        (Direction::Read, Duration::from_millis(0), (0u16..256).map(|b| b as u8).collect()),
+    ];
+
+    let mut buf = Vec::new();
+
+    for &(dir, elapsed, ref data) in &packets {
+        writer.write_packet(&mut buf, dir, elapsed, data).unwrap();
+    }
+
+    let got: Vec<_> = DumpRead::with_formatter(Cursor::new(buf), reader).collect();
+
+    assert_eq!(got.len(), packets.len());
+
+    for (p, &(dir, elapsed, ref data)) in got.iter().zip(packets.iter()) {
+        assert_eq!(p.direction(), dir);
+        assert_eq!(p.elapsed(), elapsed);
+        assert_eq!(p.data(), &data[..]);
+    }
+}
+
+#[test]
+fn hex_round_trip() {
+    round_trip(HexFormatter::new(), HexFormatter::new());
+}
+
+#[test]
+fn json_lines_round_trip() {
+    round_trip(JsonLinesFormatter::new(), JsonLinesFormatter::new());
+}
+
+#[test]
+fn raw_round_trip() {
+    round_trip(RawFormatter::new(), RawFormatter::new());
+}